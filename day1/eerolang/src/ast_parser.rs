@@ -1,128 +1,264 @@
-use std::iter::Peekable;
+use std::{iter::Peekable, rc::Rc};
 
 use log::trace;
 
-use crate::tokenizer::{Operator, Token, Value};
+use crate::{
+    error::{Error, Span},
+    tokenizer::{Operator, Token, TokenKind, UnaryOperator, Value},
+};
 
 #[derive(Debug)]
 pub enum AstNode {
     Assign(String, Box<AstNode>),
-    FunctionCall(String, Vec<AstNode>),
-    BinaryOp(Box<AstNode>, Operator, Box<AstNode>),
+    FunctionCall(String, Vec<AstNode>, Span),
+    FunctionDef(String, Vec<String>, Rc<Vec<AstNode>>),
+    Return(Box<AstNode>),
+    If(Box<AstNode>, Rc<Vec<AstNode>>, Option<Rc<Vec<AstNode>>>, Span),
+    ForIn(String, Box<AstNode>, Rc<Vec<AstNode>>, Span),
+    Index(Box<AstNode>, Box<AstNode>, Span),
+    IndexAssign(Box<AstNode>, Box<AstNode>, Box<AstNode>, Span),
+    UnaryOp(UnaryOperator, Box<AstNode>, Span),
+    BinaryOp(Box<AstNode>, Operator, Box<AstNode>, Span),
     List(Vec<AstNode>),
     Literal(Value),
-    Variable(String),
+    Variable(String, Span),
+    /// A standalone expression used as a block item, e.g. a bare `xs[0]`, a
+    /// function-call statement like `print(x)`, or a REPL input that isn't an
+    /// assignment.
+    ExprStatement(Box<AstNode>),
 }
 
-fn parse_comma_separated_list<'a, I: Iterator<Item = &'a Token> + Clone>(
-    iter: &mut Peekable<I>,
-    end_token: Token,
-) -> Vec<AstNode> {
+/// Wraps the token iterator and remembers the span of the last token it
+/// actually handed out, so an unexpected end of input can still be blamed on
+/// a real source position instead of falling back to a meaningless default.
+struct Tokens<'a> {
+    iter: Peekable<std::slice::Iter<'a, Token>>,
+    last_span: Span,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(tokens: &'a [Token]) -> Self {
+        Tokens {
+            iter: tokens.iter().peekable(),
+            last_span: Span::default(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<&'a Token> {
+        self.iter.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.iter.next();
+        if let Some(token) = token {
+            self.last_span = token.span;
+        }
+        token
+    }
+
+    /// Builds an `UnexpectedToken` error for `found`, using the span of the
+    /// last token actually consumed when `found` is `None` (end of input)
+    /// instead of defaulting to an uninformative line 0.
+    fn unexpected(&self, found: Option<&Token>) -> Error {
+        match found {
+            Some(token) => Error::UnexpectedToken {
+                found: format!("{:?}", token.kind),
+                span: token.span,
+            },
+            None => Error::UnexpectedToken {
+                found: "end of input".to_owned(),
+                span: self.last_span,
+            },
+        }
+    }
+}
+
+fn expect(iter: &mut Tokens, kind: TokenKind) -> Result<Span, Error> {
+    match iter.next() {
+        Some(token) if token.kind == kind => Ok(token.span),
+        found => Err(iter.unexpected(found)),
+    }
+}
+
+fn parse_comma_separated_list(
+    iter: &mut Tokens,
+    end_kind: TokenKind,
+) -> Result<Vec<AstNode>, Error> {
     let mut elements = Vec::new();
     loop {
-        let next = iter.peek();
-        if next == Some(&&end_token) {
+        if iter.peek().map(|t| &t.kind) == Some(&end_kind) {
             iter.next();
             break;
         }
-        let element = parse_expression(iter).unwrap();
+        let element = parse_expression(iter)?;
         elements.push(element);
-        let next = iter.peek();
-        if let Some(Token::Comma) = next {
-            iter.next();
-        } else if next == Some(&&end_token) {
+        match iter.peek().map(|t| &t.kind) {
+            Some(TokenKind::Comma) => {
+                iter.next();
+            }
+            Some(k) if *k == end_kind => {
+                iter.next();
+                break;
+            }
+            _ => {
+                let found = iter.next();
+                return Err(iter.unexpected(found));
+            }
+        }
+    }
+    Ok(elements)
+}
+
+fn parse_ident_list(iter: &mut Tokens, end_kind: TokenKind) -> Result<Vec<String>, Error> {
+    let mut idents = Vec::new();
+    loop {
+        if iter.peek().map(|t| &t.kind) == Some(&end_kind) {
             iter.next();
             break;
-        } else {
-            panic!("Expected ',' or ']/)' in list");
+        }
+        let found = iter.next();
+        let Some(Token {
+            kind: TokenKind::Ident(ident),
+            ..
+        }) = found
+        else {
+            return Err(iter.unexpected(found));
+        };
+        idents.push(ident.clone());
+        match iter.peek().map(|t| &t.kind) {
+            Some(TokenKind::Comma) => {
+                iter.next();
+            }
+            Some(k) if *k == end_kind => {
+                iter.next();
+                break;
+            }
+            _ => {
+                let found = iter.next();
+                return Err(iter.unexpected(found));
+            }
         }
     }
-    elements
+    Ok(idents)
 }
 
-fn parse_function_call<'a, I: Iterator<Item = &'a Token> + Clone>(
+fn parse_function_call(
     ident: &str,
-    iter: &mut Peekable<I>,
-) -> Option<AstNode> {
-    let next = iter.peek();
-    let Some(Token::LParen) = next else {
-        return None;
-    };
+    span: Span,
+    iter: &mut Tokens,
+) -> Result<Option<AstNode>, Error> {
+    if iter.peek().map(|t| &t.kind) != Some(&TokenKind::LParen) {
+        return Ok(None);
+    }
     iter.next();
-    let args = parse_comma_separated_list(iter, Token::RParen);
-    Some(AstNode::FunctionCall(ident.to_owned(), args))
+    let args = parse_comma_separated_list(iter, TokenKind::RParen)?;
+    Ok(Some(AstNode::FunctionCall(ident.to_owned(), args, span)))
+}
+
+fn parse_index_suffix(iter: &mut Tokens, mut node: AstNode) -> Result<AstNode, Error> {
+    while let Some(token) = iter.peek() {
+        if token.kind != TokenKind::LSquareParen {
+            break;
+        }
+        let span = token.span;
+        iter.next();
+        let index_expr = parse_expression(iter)?;
+        expect(iter, TokenKind::RSquareParen)?;
+        node = AstNode::Index(Box::new(node), Box::new(index_expr), span);
+    }
+    Ok(node)
 }
 
-fn parse_primary_expression<'a, I: Iterator<Item = &'a Token> + Clone>(
-    iter: &mut Peekable<I>,
-) -> Option<AstNode> {
-    let token = iter.peek()?;
+fn parse_primary_expression(iter: &mut Tokens) -> Result<Option<AstNode>, Error> {
+    let Some(token) = iter.peek() else {
+        return Ok(None);
+    };
 
-    match token {
-        Token::Literal(lit) => {
+    if matches!(
+        token.kind,
+        TokenKind::Operator(Operator::Minus) | TokenKind::Bang
+    ) {
+        let op = match token.kind {
+            TokenKind::Operator(Operator::Minus) => UnaryOperator::Neg,
+            TokenKind::Bang => UnaryOperator::Not,
+            _ => unreachable!(),
+        };
+        let span = token.span;
+        iter.next();
+        let found = iter.peek();
+        let operand = parse_primary_expression(iter)?.ok_or_else(|| iter.unexpected(found))?;
+        return Ok(Some(AstNode::UnaryOp(op, Box::new(operand), span)));
+    }
+
+    let node = match &token.kind {
+        TokenKind::Literal(lit) => {
+            let lit = lit.clone();
             iter.next();
-            Some(AstNode::Literal(lit.clone()))
+            AstNode::Literal(lit)
         }
-        Token::Ident(ident) => {
+        TokenKind::Ident(ident) => {
+            let ident = ident.clone();
+            let span = token.span;
             iter.next();
             trace!("Parsing identifier: {}", ident);
-            if let Some(fcall) = parse_function_call(ident, iter) {
-                Some(fcall)
+            if let Some(fcall) = parse_function_call(&ident, span, iter)? {
+                fcall
             } else {
-                Some(AstNode::Variable(ident.clone()))
+                AstNode::Variable(ident, span)
             }
         }
-        Token::LParen => {
+        TokenKind::LParen => {
             iter.next();
-            let expr = parse_expression(iter).unwrap();
-            let next = iter.peek().unwrap();
-            if next != &&Token::RParen {
-                panic!("Expected closing parenthesis, found: {:?}", next);
-            }
-            iter.next();
-            Some(expr)
+            let expr = parse_expression(iter)?;
+            expect(iter, TokenKind::RParen)?;
+            expr
         }
-        Token::LSquareParen => {
+        TokenKind::LSquareParen => {
             iter.next();
-            let elements = parse_comma_separated_list(iter, Token::RSquareParen);
-            Some(AstNode::List(elements))
+            let elements = parse_comma_separated_list(iter, TokenKind::RSquareParen)?;
+            AstNode::List(elements)
         }
-        _ => None,
-    }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(parse_index_suffix(iter, node)?))
 }
-//
 
-fn parse_expression<'a, I: Iterator<Item = &'a Token> + Clone>(
-    iter: &mut Peekable<I>,
-) -> Option<AstNode> {
-    let left = parse_primary_expression(iter)?;
+fn parse_expression(iter: &mut Tokens) -> Result<AstNode, Error> {
+    let found = iter.peek();
+    let left = parse_primary_expression(iter)?.ok_or_else(|| iter.unexpected(found))?;
     trace!("Parsed primary expression: {:?}", left);
     parse_expression_impl(iter, left, 0)
 }
 
-fn parse_expression_impl<'a, I: Iterator<Item = &'a Token> + Clone>(
-    iter: &mut Peekable<I>,
+fn parse_expression_impl(
+    iter: &mut Tokens,
     mut left: AstNode,
     min_precedence: u8,
-) -> Option<AstNode> {
-    while let Some(Token::Operator(op)) = iter.peek() {
+) -> Result<AstNode, Error> {
+    while let Some(token) = iter.peek() {
+        let TokenKind::Operator(op) = token.kind else {
+            break;
+        };
         if op.precedence() < min_precedence {
             break;
         }
-        let op = *op;
+        let span = token.span;
         iter.next();
-        let mut right =
-            parse_primary_expression(iter).expect("Expected an expression after operator");
+        let found = iter.peek();
+        let mut right = parse_primary_expression(iter)?.ok_or_else(|| iter.unexpected(found))?;
 
         trace!(
             "Parsed right-hand side expression: {:?} after op, {:?}",
             right, op
         );
 
-        while let Some(Token::Operator(next_op)) = iter.peek() {
+        while let Some(next_token) = iter.peek() {
+            let TokenKind::Operator(next_op) = next_token.kind else {
+                break;
+            };
             if next_op.precedence() > op.precedence() {
-                right = parse_expression_impl(iter, right, next_op.precedence())
-                    .expect("Expected expression after operator");
+                right = parse_expression_impl(iter, right, next_op.precedence())?;
                 trace!(
                     "Updated right-hand side expression to: {:?} after parsing higher precedence op {:?}",
                     right, next_op
@@ -132,42 +268,224 @@ fn parse_expression_impl<'a, I: Iterator<Item = &'a Token> + Clone>(
             }
         }
 
-        left = AstNode::BinaryOp(Box::new(left), op, Box::new(right));
+        left = AstNode::BinaryOp(Box::new(left), op, Box::new(right), span);
     }
 
-    Some(left)
+    Ok(left)
 }
 
-pub fn parse(tokens: &[Token]) -> Vec<AstNode> {
-    let mut iter = tokens.iter().peekable();
+fn parse_function_def(iter: &mut Tokens) -> Result<AstNode, Error> {
+    let found = iter.next();
+    let Some(Token {
+        kind: TokenKind::Ident(name),
+        ..
+    }) = found
+    else {
+        return Err(iter.unexpected(found));
+    };
+    let name = name.clone();
+    expect(iter, TokenKind::LParen)?;
+    let params = parse_ident_list(iter, TokenKind::RParen)?;
+    expect(iter, TokenKind::LBrace)?;
+    let body = parse_block(iter, true)?;
+    Ok(AstNode::FunctionDef(name, params, Rc::new(body)))
+}
 
-    let mut block = Vec::new();
+fn parse_if(iter: &mut Tokens, span: Span) -> Result<AstNode, Error> {
+    let cond = parse_expression(iter)?;
+    expect(iter, TokenKind::LBrace)?;
+    let then_block = parse_block(iter, true)?;
+
+    let else_block = if iter.peek().map(|t| &t.kind) == Some(&TokenKind::KeywordElse) {
+        iter.next();
+        match iter.next() {
+            Some(Token {
+                kind: TokenKind::LBrace,
+                ..
+            }) => Some(parse_block(iter, true)?),
+            Some(Token {
+                kind: TokenKind::KeywordIf,
+                span: else_if_span,
+            }) => Some(vec![parse_if(iter, *else_if_span)?]),
+            found => return Err(iter.unexpected(found)),
+        }
+    } else {
+        None
+    };
+
+    Ok(AstNode::If(
+        Box::new(cond),
+        Rc::new(then_block),
+        else_block.map(Rc::new),
+        span,
+    ))
+}
+
+fn parse_for_in(iter: &mut Tokens, span: Span) -> Result<AstNode, Error> {
+    let found = iter.next();
+    let Some(Token {
+        kind: TokenKind::Ident(var),
+        ..
+    }) = found
+    else {
+        return Err(iter.unexpected(found));
+    };
+    let var = var.clone();
+    expect(iter, TokenKind::KeywordIn)?;
+    let iterable = parse_expression(iter)?;
+    expect(iter, TokenKind::LBrace)?;
+    let body = parse_block(iter, true)?;
+    Ok(AstNode::ForIn(var, Box::new(iterable), Rc::new(body), span))
+}
+
+fn parse_statement(iter: &mut Tokens) -> Result<Option<AstNode>, Error> {
+    let Some(token) = iter.peek() else {
+        return Ok(None);
+    };
 
-    while let Some(token) = iter.next() {
-        match token {
-            Token::Ident(ident) => match iter.peek().copied().unwrap() {
-                Token::Assign => {
-                    trace!("Parsing assignment to {}", ident);
+    let node = match &token.kind {
+        TokenKind::KeywordFn => {
+            iter.next();
+            parse_function_def(iter)?
+        }
+        TokenKind::KeywordReturn => {
+            iter.next();
+            let expr = parse_expression(iter)?;
+            AstNode::Return(Box::new(expr))
+        }
+        TokenKind::KeywordIf => {
+            let span = token.span;
+            iter.next();
+            parse_if(iter, span)?
+        }
+        TokenKind::KeywordFor => {
+            let span = token.span;
+            iter.next();
+            parse_for_in(iter, span)?
+        }
+        TokenKind::Ident(ident) => {
+            let ident = ident.clone();
+            let span = token.span;
+            iter.next();
+            if iter.peek().map(|t| &t.kind) == Some(&TokenKind::LSquareParen) {
+                let mut indices = Vec::new();
+                while iter.peek().map(|t| &t.kind) == Some(&TokenKind::LSquareParen) {
                     iter.next();
-                    let expr = parse_expression(&mut iter).unwrap();
-                    block.push(AstNode::Assign(ident.clone(), Box::new(expr)));
+                    let index_expr = parse_expression(iter)?;
+                    expect(iter, TokenKind::RSquareParen)?;
+                    indices.push(index_expr);
                 }
-                t => {
-                    trace!("Parsing function call starting with identifier {}", ident);
-                    if let Some(f) = parse_function_call(ident, &mut iter) {
-                        block.push(f);
-                    } else {
-                        panic!("Token not allowed after identifier: {:?}", t);
+                if iter.peek().map(|t| &t.kind) == Some(&TokenKind::Assign) {
+                    iter.next();
+                    let last_index = indices.pop().expect("Expected at least one index");
+                    let mut list_expr = AstNode::Variable(ident, span);
+                    for index in indices {
+                        list_expr = AstNode::Index(Box::new(list_expr), Box::new(index), span);
+                    }
+                    let value = parse_expression(iter)?;
+                    AstNode::IndexAssign(
+                        Box::new(list_expr),
+                        Box::new(last_index),
+                        Box::new(value),
+                        span,
+                    )
+                } else {
+                    let mut expr = AstNode::Variable(ident, span);
+                    for index in indices {
+                        expr = AstNode::Index(Box::new(expr), Box::new(index), span);
                     }
+                    let expr = parse_expression_impl(iter, expr, 0)?;
+                    AstNode::ExprStatement(Box::new(expr))
                 }
-            },
-            Token::Eol => {}
-            t => {
-                println!("{:#?}", block);
-                panic!("Token not allowed: {:?}", t);
+            } else {
+                match iter.peek().map(|t| &t.kind) {
+                    Some(TokenKind::Assign) => {
+                        trace!("Parsing assignment to {}", ident);
+                        iter.next();
+                        let expr = parse_expression(iter)?;
+                        AstNode::Assign(ident, Box::new(expr))
+                    }
+                    _ => {
+                        trace!("Parsing function call starting with identifier {}", ident);
+                        let node = if let Some(fcall) = parse_function_call(&ident, span, iter)? {
+                            fcall
+                        } else {
+                            AstNode::Variable(ident, span)
+                        };
+                        let node = parse_index_suffix(iter, node)?;
+                        let node = parse_expression_impl(iter, node, 0)?;
+                        AstNode::ExprStatement(Box::new(node))
+                    }
+                }
+            }
+        }
+        _ => {
+            let expr = parse_expression(iter)?;
+            AstNode::ExprStatement(Box::new(expr))
+        }
+    };
+
+    Ok(Some(node))
+}
+
+/// Parses statements until a closing `}` or, only when `require_closing` is
+/// false (the top-level program), end of input. Nested blocks (function
+/// bodies, `if`/`for` bodies) pass `true` so a missing `}` is reported as an
+/// error instead of silently truncating the body.
+fn parse_block(iter: &mut Tokens, require_closing: bool) -> Result<Vec<AstNode>, Error> {
+    let mut block = Vec::new();
+
+    loop {
+        match iter.peek().map(|t| &t.kind) {
+            None if require_closing => return Err(iter.unexpected(None)),
+            None => break,
+            Some(TokenKind::RBrace) => {
+                iter.next();
+                break;
+            }
+            Some(_) => {
+                let statement = parse_statement(iter)?.ok_or_else(|| iter.unexpected(None))?;
+                block.push(statement);
             }
         }
     }
 
-    block
+    Ok(block)
+}
+
+pub fn parse(tokens: &[Token]) -> Result<Vec<AstNode>, Error> {
+    let mut iter = Tokens::new(tokens);
+    parse_block(&mut iter, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::tokenize;
+
+    #[test]
+    fn if_and_for_in_capture_the_keyword_span() {
+        let tokens = tokenize("x = 1\nif x {\n}\nfor i in x {\n}").unwrap();
+        let block = parse(&tokens).unwrap();
+        let AstNode::If(_, _, _, if_span) = &block[1] else {
+            panic!("expected If node, got {:?}", block[1]);
+        };
+        assert_eq!(*if_span, Span::new(2, 1));
+
+        let AstNode::ForIn(_, _, _, for_span) = &block[2] else {
+            panic!("expected ForIn node, got {:?}", block[2]);
+        };
+        assert_eq!(*for_span, Span::new(4, 1));
+    }
+
+    #[test]
+    fn unexpected_end_of_input_blames_the_last_consumed_token() {
+        let tokens = tokenize("x = ").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert_eq!(err.span(), Span::new(1, 3));
+
+        let tokens = tokenize("fn f() {\nx = 1\n").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert_eq!(err.span(), Span::new(2, 5));
+    }
 }
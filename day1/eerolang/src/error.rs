@@ -0,0 +1,218 @@
+use std::fmt;
+
+/// A 1-indexed line/column position in the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize) -> Self {
+        Span { line, column }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    UndefinedVariable {
+        name: String,
+        span: Span,
+    },
+    UndefinedFunction {
+        name: String,
+        span: Span,
+    },
+    TypeMismatch {
+        expected: String,
+        actual: String,
+        span: Span,
+    },
+    WrongArgCount {
+        expected: usize,
+        actual: usize,
+        span: Span,
+    },
+    UnexpectedToken {
+        found: String,
+        span: Span,
+    },
+    UnexpectedChar {
+        ch: char,
+        span: Span,
+    },
+    DivideByZero {
+        span: Span,
+    },
+    Overflow {
+        span: Span,
+    },
+    Builtin {
+        message: String,
+        span: Span,
+    },
+}
+
+impl Error {
+    pub fn span(&self) -> Span {
+        match self {
+            Error::UndefinedVariable { span, .. }
+            | Error::UndefinedFunction { span, .. }
+            | Error::TypeMismatch { span, .. }
+            | Error::WrongArgCount { span, .. }
+            | Error::UnexpectedToken { span, .. }
+            | Error::UnexpectedChar { span, .. }
+            | Error::DivideByZero { span }
+            | Error::Overflow { span }
+            | Error::Builtin { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let span = self.span();
+        write!(f, "line {}: ", span.line)?;
+        match self {
+            Error::UndefinedVariable { name, .. } => write!(f, "undefined variable '{}'", name),
+            Error::UndefinedFunction { name, .. } => write!(f, "undefined function '{}'", name),
+            Error::TypeMismatch {
+                expected, actual, ..
+            } => write!(f, "type mismatch: expected {}, found {}", expected, actual),
+            Error::WrongArgCount {
+                expected, actual, ..
+            } => write!(
+                f,
+                "wrong number of arguments: expected {}, got {}",
+                expected, actual
+            ),
+            Error::UnexpectedToken { found, .. } => write!(f, "unexpected token: {}", found),
+            Error::UnexpectedChar { ch, .. } => write!(f, "unexpected character: '{}'", ch),
+            Error::DivideByZero { .. } => write!(f, "division by zero"),
+            Error::Overflow { .. } => write!(f, "arithmetic overflow"),
+            Error::Builtin { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn span_reports_line_and_column() {
+        let span = Span::new(3, 7);
+        assert_eq!(span.line, 3);
+        assert_eq!(span.column, 7);
+    }
+
+    #[test]
+    fn each_variant_reports_its_own_span() {
+        let span = Span::new(5, 1);
+        let variants = [
+            Error::UndefinedVariable {
+                name: "x".to_owned(),
+                span,
+            },
+            Error::UndefinedFunction {
+                name: "f".to_owned(),
+                span,
+            },
+            Error::TypeMismatch {
+                expected: "bool".to_owned(),
+                actual: "integer".to_owned(),
+                span,
+            },
+            Error::WrongArgCount {
+                expected: 1,
+                actual: 2,
+                span,
+            },
+            Error::UnexpectedToken {
+                found: "RBrace".to_owned(),
+                span,
+            },
+            Error::UnexpectedChar { ch: '$', span },
+            Error::DivideByZero { span },
+            Error::Overflow { span },
+            Error::Builtin {
+                message: "boom".to_owned(),
+                span,
+            },
+        ];
+        for variant in variants {
+            assert_eq!(variant.span(), span);
+        }
+    }
+
+    #[test]
+    fn display_prefixes_line_number() {
+        let span = Span::new(42, 1);
+        let err = Error::UndefinedVariable {
+            name: "foo".to_owned(),
+            span,
+        };
+        assert_eq!(err.to_string(), "line 42: undefined variable 'foo'");
+    }
+
+    #[test]
+    fn display_messages_match_variant() {
+        let span = Span::default();
+        assert_eq!(
+            Error::UndefinedFunction {
+                name: "bar".to_owned(),
+                span
+            }
+            .to_string(),
+            "line 0: undefined function 'bar'"
+        );
+        assert_eq!(
+            Error::TypeMismatch {
+                expected: "bool".to_owned(),
+                actual: "integer".to_owned(),
+                span
+            }
+            .to_string(),
+            "line 0: type mismatch: expected bool, found integer"
+        );
+        assert_eq!(
+            Error::WrongArgCount {
+                expected: 1,
+                actual: 2,
+                span
+            }
+            .to_string(),
+            "line 0: wrong number of arguments: expected 1, got 2"
+        );
+        assert_eq!(
+            Error::UnexpectedToken {
+                found: "Comma".to_owned(),
+                span
+            }
+            .to_string(),
+            "line 0: unexpected token: Comma"
+        );
+        assert_eq!(
+            Error::UnexpectedChar { ch: '@', span }.to_string(),
+            "line 0: unexpected character: '@'"
+        );
+        assert_eq!(
+            Error::DivideByZero { span }.to_string(),
+            "line 0: division by zero"
+        );
+        assert_eq!(
+            Error::Overflow { span }.to_string(),
+            "line 0: arithmetic overflow"
+        );
+        assert_eq!(
+            Error::Builtin {
+                message: "failed to read file 'x'".to_owned(),
+                span
+            }
+            .to_string(),
+            "line 0: failed to read file 'x'"
+        );
+    }
+}
@@ -1,29 +1,116 @@
-use log::error;
+use std::io::BufRead;
 
 use crate::program::Program;
 
 mod ast_parser;
+mod error;
 mod program;
 mod tokenizer;
 
+/// Which pipeline stage to stop at and print, selected via `--tokens`/`--ast`.
+#[derive(Debug, PartialEq)]
+enum InspectMode {
+    None,
+    Tokens,
+    Ast,
+}
+
+/// Scans CLI arguments (excluding argv[0]) for `--tokens`/`--ast` flags and a
+/// source file, in any order. The last inspect flag seen wins.
+fn parse_args(args: &[String]) -> (InspectMode, Option<&str>) {
+    let mut inspect = InspectMode::None;
+    let mut source_file = None;
+    for arg in args {
+        match arg.as_str() {
+            "--tokens" => inspect = InspectMode::Tokens,
+            "--ast" => inspect = InspectMode::Ast,
+            other => source_file = Some(other),
+        }
+    }
+    (inspect, source_file)
+}
+
+fn run(source_file: &str, inspect: InspectMode) -> Result<(), error::Error> {
+    let source_code =
+        std::fs::read_to_string(source_file).map_err(|e| error::Error::Builtin {
+            message: format!("failed to read source file '{}': {}", source_file, e),
+            span: error::Span::default(),
+        })?;
+
+    let tokens = tokenizer::tokenize(&source_code)?;
+    if let InspectMode::Tokens = inspect {
+        println!("{:#?}", tokens);
+        return Ok(());
+    }
+
+    let block = ast_parser::parse(&tokens)?;
+    if let InspectMode::Ast = inspect {
+        println!("{:#?}", block);
+        return Ok(());
+    }
+
+    let mut program = Program::new(block);
+    program.execute()
+}
+
+/// Reads statements from stdin until EOF, evaluating each one against a
+/// single retained `Program` so variables and functions persist across lines.
+fn repl() {
+    let mut program = Program::new(Vec::new());
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        if let Err(err) = program.eval_line(&line) {
+            eprintln!("error: {}", err);
+        }
+    }
+}
+
 fn main() {
     env_logger::builder().format_timestamp(None).init();
 
     let args = std::env::args().collect::<Vec<String>>();
-    if args.len() < 2 {
-        error!("Usage: {} <source_file>", args[0]);
+    let (inspect, source_file) = parse_args(&args[1..]);
+
+    let Some(source_file) = source_file else {
+        repl();
         return;
+    };
+
+    if let Err(err) = run(source_file, inspect) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
     }
+}
 
-    let source_file = &args[1];
-    let source_code = std::fs::read_to_string(source_file).expect("Failed to read source file");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let tokens = tokenizer::tokenize(&source_code);
-    // for token in &tokens {
-    //     println!("{:?}", token);
-    // }
+    fn args(strs: &[&str]) -> Vec<String> {
+        strs.iter().map(|s| s.to_string()).collect()
+    }
 
-    let block = ast_parser::parse(&tokens);
-    let mut program = Program::new(block);
-    program.execute();
+    #[test]
+    fn flag_after_filename_is_still_recognized() {
+        let a = args(&["script.eero", "--tokens"]);
+        let (inspect, source_file) = parse_args(&a);
+        assert_eq!(inspect, InspectMode::Tokens);
+        assert_eq!(source_file, Some("script.eero"));
+    }
+
+    #[test]
+    fn last_inspect_flag_wins_when_both_are_given() {
+        let a = args(&["--tokens", "--ast", "script.eero"]);
+        let (inspect, source_file) = parse_args(&a);
+        assert_eq!(inspect, InspectMode::Ast);
+        assert_eq!(source_file, Some("script.eero"));
+    }
+
+    #[test]
+    fn flag_with_no_file_leaves_source_file_none() {
+        let a = args(&["--ast"]);
+        let (inspect, source_file) = parse_args(&a);
+        assert_eq!(inspect, InspectMode::Ast);
+        assert_eq!(source_file, None);
+    }
 }
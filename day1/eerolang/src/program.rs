@@ -1,26 +1,40 @@
-use std::{collections::HashMap, io::Write, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, io::Write, rc::Rc};
 
 use log::trace;
 
 use crate::{
-    ast_parser::AstNode,
-    tokenizer::{Operator, Value},
+    ast_parser::{self, AstNode},
+    error::{Error, Span},
+    tokenizer::{self, Operator, UnaryOperator, Value},
 };
 
-fn builtin_print(args: &mut [Value]) -> Option<Value> {
+fn value_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Integer(_) => "integer",
+        Value::Float(_) => "float",
+        Value::String(_) => "string",
+        Value::Bool(_) => "bool",
+        Value::List(_) => "list",
+    }
+}
+
+fn builtin_print(args: &mut [Value], _span: Span) -> Result<Option<Value>, Error> {
     let mut w = std::io::stdout();
     for (i, arg) in args.iter().enumerate() {
         match arg {
             Value::Integer(i) => write!(&mut w, "{}", i).unwrap(),
             Value::Float(f) => write!(&mut w, "{}", f).unwrap(),
             Value::String(s) => write!(&mut w, "\"{}\"", s).unwrap(),
+            Value::Bool(b) => write!(&mut w, "{}", b).unwrap(),
             Value::List(l) => {
+                let l = l.borrow();
                 write!(&mut w, "[").unwrap();
                 for (j, item) in l.iter().enumerate() {
                     match item {
                         Value::Integer(ii) => write!(&mut w, "{}", ii).unwrap(),
                         Value::Float(ff) => write!(&mut w, "{}", ff).unwrap(),
                         Value::String(ss) => write!(&mut w, "\"{}\"", ss).unwrap(),
+                        Value::Bool(bb) => write!(&mut w, "{}", bb).unwrap(),
                         Value::List(_) => write!(&mut w, "<nested list>").unwrap(),
                     }
                     if j < l.len() - 1 {
@@ -36,25 +50,51 @@ fn builtin_print(args: &mut [Value]) -> Option<Value> {
     }
     writeln!(&mut w).unwrap();
     w.flush().unwrap();
-    None
+    Ok(None)
 }
 
-fn builtin_readfile(args: &mut [Value]) -> Option<Value> {
-    assert_eq!(args.len(), 1);
-
-    let [Value::String(filename)] = &args else {
-        panic!("readfile expects (string), got {:?}", args)
+fn builtin_readfile(args: &mut [Value], span: Span) -> Result<Option<Value>, Error> {
+    if args.len() != 1 {
+        return Err(Error::WrongArgCount {
+            expected: 1,
+            actual: args.len(),
+            span,
+        });
+    }
+    let Value::String(filename) = &args[0] else {
+        return Err(Error::TypeMismatch {
+            expected: "string".to_owned(),
+            actual: value_type_name(&args[0]).to_owned(),
+            span,
+        });
     };
 
-    let content = std::fs::read_to_string(filename.as_ref())
-        .unwrap_or_else(|_| panic!("Failed to read file: {}", filename));
+    let content = std::fs::read_to_string(filename.as_ref()).map_err(|e| Error::Builtin {
+        message: format!("failed to read file '{}': {}", filename, e),
+        span,
+    })?;
 
-    Some(Value::String(content.trim().into()))
+    Ok(Some(Value::String(content.trim().into())))
 }
 
-fn builtin_split(args: &mut [Value]) -> Option<Value> {
-    let [Value::String(s), Value::String(delim)] = &args else {
-        panic!("split expects (string, string), got {:?}", args)
+fn builtin_split(args: &mut [Value], span: Span) -> Result<Option<Value>, Error> {
+    if args.len() != 2 {
+        return Err(Error::WrongArgCount {
+            expected: 2,
+            actual: args.len(),
+            span,
+        });
+    }
+    let (Value::String(s), Value::String(delim)) = (&args[0], &args[1]) else {
+        return Err(Error::TypeMismatch {
+            expected: "(string, string)".to_owned(),
+            actual: format!(
+                "({}, {})",
+                value_type_name(&args[0]),
+                value_type_name(&args[1])
+            ),
+            span,
+        });
     };
 
     trace!("Splitting string '{}' by delimiter '{}'", s, delim);
@@ -64,24 +104,165 @@ fn builtin_split(args: &mut [Value]) -> Option<Value> {
         .map(|part| Value::String(Rc::from(part)))
         .collect();
 
-    Some(Value::List(parts))
+    Ok(Some(Value::List(Rc::new(RefCell::new(parts)))))
 }
 
-fn builtin_len(args: &mut [Value]) -> Option<Value> {
-    assert_eq!(args.len(), 1, "len expects 1 argument");
+fn builtin_range(args: &mut [Value], span: Span) -> Result<Option<Value>, Error> {
+    let (start, end) = match args {
+        [Value::Integer(end)] => (0, *end),
+        [Value::Integer(start), Value::Integer(end)] => (*start, *end),
+        _ => {
+            return Err(Error::TypeMismatch {
+                expected: "(integer) or (integer, integer)".to_owned(),
+                actual: format!(
+                    "({})",
+                    args.iter().map(value_type_name).collect::<Vec<_>>().join(", ")
+                ),
+                span,
+            });
+        }
+    };
+
+    let values = (start..end).map(Value::Integer).collect::<Vec<_>>();
+    Ok(Some(Value::List(Rc::new(RefCell::new(values)))))
+}
+
+fn builtin_len(args: &mut [Value], span: Span) -> Result<Option<Value>, Error> {
+    if args.len() != 1 {
+        return Err(Error::WrongArgCount {
+            expected: 1,
+            actual: args.len(),
+            span,
+        });
+    }
 
     match &args[0] {
-        Value::String(s) => Some(Value::Integer(s.len() as i64)),
-        Value::List(l) => Some(Value::Integer(l.len() as i64)),
-        _ => panic!("len expects (string) or (list), got {:?}", args),
+        Value::String(s) => Ok(Some(Value::Integer(s.len() as i64))),
+        Value::List(l) => Ok(Some(Value::Integer(l.borrow().len() as i64))),
+        other => Err(Error::TypeMismatch {
+            expected: "string or list".to_owned(),
+            actual: value_type_name(other).to_owned(),
+            span,
+        }),
     }
 }
 
-pub type ProgramFn = fn(&mut [Value]) -> Option<Value>;
+fn builtin_input(args: &mut [Value], span: Span) -> Result<Option<Value>, Error> {
+    if !args.is_empty() {
+        return Err(Error::WrongArgCount {
+            expected: 0,
+            actual: args.len(),
+            span,
+        });
+    }
+
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| Error::Builtin {
+            message: format!("failed to read from stdin: {}", e),
+            span,
+        })?;
+
+    Ok(Some(Value::String(line.trim_end_matches(['\n', '\r']).into())))
+}
+
+fn builtin_ord(args: &mut [Value], span: Span) -> Result<Option<Value>, Error> {
+    if args.len() != 1 {
+        return Err(Error::WrongArgCount {
+            expected: 1,
+            actual: args.len(),
+            span,
+        });
+    }
+    let Value::String(s) = &args[0] else {
+        return Err(Error::TypeMismatch {
+            expected: "string".to_owned(),
+            actual: value_type_name(&args[0]).to_owned(),
+            span,
+        });
+    };
+
+    let ch = s.chars().next().ok_or_else(|| Error::Builtin {
+        message: "ord expects a non-empty string".to_owned(),
+        span,
+    })?;
+
+    Ok(Some(Value::Integer(ch as i64)))
+}
+
+fn builtin_chr(args: &mut [Value], span: Span) -> Result<Option<Value>, Error> {
+    if args.len() != 1 {
+        return Err(Error::WrongArgCount {
+            expected: 1,
+            actual: args.len(),
+            span,
+        });
+    }
+    let Value::Integer(code) = &args[0] else {
+        return Err(Error::TypeMismatch {
+            expected: "integer".to_owned(),
+            actual: value_type_name(&args[0]).to_owned(),
+            span,
+        });
+    };
+
+    let ch = char::from_u32(*code as u32).ok_or_else(|| Error::Builtin {
+        message: format!("chr expects a valid Unicode codepoint, got {}", code),
+        span,
+    })?;
+
+    Ok(Some(Value::String(ch.to_string().into())))
+}
+
+fn builtin_join(args: &mut [Value], span: Span) -> Result<Option<Value>, Error> {
+    if args.len() != 2 {
+        return Err(Error::WrongArgCount {
+            expected: 2,
+            actual: args.len(),
+            span,
+        });
+    }
+    let (Value::List(list), Value::String(sep)) = (&args[0], &args[1]) else {
+        return Err(Error::TypeMismatch {
+            expected: "(list, string)".to_owned(),
+            actual: format!(
+                "({}, {})",
+                value_type_name(&args[0]),
+                value_type_name(&args[1])
+            ),
+            span,
+        });
+    };
+
+    let mut parts = Vec::with_capacity(list.borrow().len());
+    for item in list.borrow().iter() {
+        match item {
+            Value::String(s) => parts.push(s.to_string()),
+            other => {
+                return Err(Error::TypeMismatch {
+                    expected: "a list of strings".to_owned(),
+                    actual: value_type_name(other).to_owned(),
+                    span,
+                });
+            }
+        }
+    }
+
+    Ok(Some(Value::String(parts.join(sep.as_ref()).into())))
+}
+
+pub type ProgramFn = fn(&mut [Value], Span) -> Result<Option<Value>, Error>;
+
+struct FunctionDef {
+    params: Vec<String>,
+    body: Rc<Vec<AstNode>>,
+}
 
 pub struct Program {
     block: Rc<Vec<AstNode>>,
-    vars: HashMap<String, Value>,
+    vars: Vec<HashMap<String, Value>>,
+    functions: HashMap<String, Rc<FunctionDef>>,
     builtins: HashMap<String, ProgramFn>,
 }
 
@@ -92,44 +273,158 @@ impl Program {
         builtins.insert("readfile".to_owned(), builtin_readfile);
         builtins.insert("split".to_owned(), builtin_split);
         builtins.insert("len".to_owned(), builtin_len);
+        builtins.insert("range".to_owned(), builtin_range);
+        builtins.insert("input".to_owned(), builtin_input);
+        builtins.insert("ord".to_owned(), builtin_ord);
+        builtins.insert("chr".to_owned(), builtin_chr);
+        builtins.insert("join".to_owned(), builtin_join);
 
         Program {
             block: Rc::new(block),
-            vars: HashMap::new(),
+            vars: vec![HashMap::new()],
+            functions: HashMap::new(),
             builtins,
         }
     }
 
-    fn compute_expression<'a>(&'a self, expr: &'a AstNode) -> Value {
+    fn get_var(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.vars.last().and_then(|scope| scope.get(name)) {
+            return Some(value.clone());
+        }
+        self.vars.first().and_then(|scope| scope.get(name)).cloned()
+    }
+
+    fn compute_expression(&mut self, expr: &AstNode) -> Result<Value, Error> {
         match expr {
-            AstNode::Literal(lit) => lit.clone(),
-            AstNode::Variable(name) => self.vars.get(name).expect("Undefined variable").clone(),
-            AstNode::FunctionCall(name, args) => self
-                .call_function(name, args)
-                .expect("Function did not return a value"),
+            AstNode::Literal(lit) => Ok(lit.clone()),
+            AstNode::Variable(name, span) => {
+                self.get_var(name).ok_or_else(|| Error::UndefinedVariable {
+                    name: name.clone(),
+                    span: *span,
+                })
+            }
+            AstNode::FunctionCall(name, args, span) => {
+                self.call_function(name, args, *span)?
+                    .ok_or_else(|| Error::TypeMismatch {
+                        expected: "a value".to_owned(),
+                        actual: "nothing (function has no return value)".to_owned(),
+                        span: *span,
+                    })
+            }
             AstNode::List(list) => {
                 let values = list
                     .iter()
                     .map(|elem| self.compute_expression(elem))
-                    .collect::<Vec<_>>();
-                Value::List(values)
-            }
-            AstNode::BinaryOp(left, op, right) => {
-                let mut left_val = self.compute_expression(left);
-                let mut right_val = self.compute_expression(right);
-                if let (Value::String(l), Value::String(r), Operator::Plus) =
-                    (&left_val, &right_val, op)
-                {
-                    return Value::String(Rc::from([l.as_ref(), r.as_ref()].concat()));
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::List(Rc::new(RefCell::new(values))))
+            }
+            AstNode::Index(list_expr, index_expr, span) => {
+                let list_val = self.compute_expression(list_expr)?;
+                let Value::List(list) = list_val else {
+                    return Err(Error::TypeMismatch {
+                        expected: "list".to_owned(),
+                        actual: value_type_name(&list_val).to_owned(),
+                        span: *span,
+                    });
+                };
+                let index_val = self.compute_expression(index_expr)?;
+                let Value::Integer(index) = index_val else {
+                    return Err(Error::TypeMismatch {
+                        expected: "integer".to_owned(),
+                        actual: value_type_name(&index_val).to_owned(),
+                        span: *span,
+                    });
+                };
+                let list = list.borrow();
+                list.get(index as usize).cloned().ok_or_else(|| Error::TypeMismatch {
+                    expected: format!("index within 0..{}", list.len()),
+                    actual: index.to_string(),
+                    span: *span,
+                })
+            }
+            AstNode::UnaryOp(op, operand, span) => {
+                match (op, self.compute_expression(operand)?) {
+                    (UnaryOperator::Neg, Value::Integer(i)) => Ok(Value::Integer(-i)),
+                    (UnaryOperator::Neg, Value::Float(f)) => Ok(Value::Float(-f)),
+                    (UnaryOperator::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                    (_, other) => Err(Error::TypeMismatch {
+                        expected: "a numeric or boolean operand".to_owned(),
+                        actual: value_type_name(&other).to_owned(),
+                        span: *span,
+                    }),
+                }
+            }
+            AstNode::BinaryOp(left, op, right, span) => {
+                let mut left_val = self.compute_expression(left)?;
+                let mut right_val = self.compute_expression(right)?;
+                if let (Value::String(l), Value::String(r)) = (&left_val, &right_val) {
+                    return match op {
+                        Operator::Plus => {
+                            Ok(Value::String(Rc::from([l.as_ref(), r.as_ref()].concat())))
+                        }
+                        Operator::Equal => Ok(Value::Bool(l == r)),
+                        Operator::NotEqual => Ok(Value::Bool(l != r)),
+                        Operator::Less => Ok(Value::Bool(l < r)),
+                        Operator::Greater => Ok(Value::Bool(l > r)),
+                        Operator::LessEqual => Ok(Value::Bool(l <= r)),
+                        Operator::GreaterEqual => Ok(Value::Bool(l >= r)),
+                        _ => Err(Error::TypeMismatch {
+                            expected: "numeric operands".to_owned(),
+                            actual: "strings".to_owned(),
+                            span: *span,
+                        }),
+                    };
+                }
+
+                if let (Value::Bool(l), Value::Bool(r)) = (&left_val, &right_val) {
+                    return match op {
+                        Operator::Equal => Ok(Value::Bool(l == r)),
+                        Operator::NotEqual => Ok(Value::Bool(l != r)),
+                        _ => Err(Error::TypeMismatch {
+                            expected: "numeric operands".to_owned(),
+                            actual: "bools".to_owned(),
+                            span: *span,
+                        }),
+                    };
                 }
 
                 if let (Value::Integer(l), Value::Integer(r)) = (&left_val, &right_val) {
-                    match op {
-                        Operator::Plus => return Value::Integer(l + r),
-                        Operator::Minus => return Value::Integer(l - r),
-                        Operator::Multiply => return Value::Integer(l * r),
-                        Operator::Divide => return Value::Integer(l / r),
-                    }
+                    return match op {
+                        Operator::Plus => l
+                            .checked_add(*r)
+                            .map(Value::Integer)
+                            .ok_or(Error::Overflow { span: *span }),
+                        Operator::Minus => l
+                            .checked_sub(*r)
+                            .map(Value::Integer)
+                            .ok_or(Error::Overflow { span: *span }),
+                        Operator::Multiply => l
+                            .checked_mul(*r)
+                            .map(Value::Integer)
+                            .ok_or(Error::Overflow { span: *span }),
+                        Operator::Divide => {
+                            if *r == 0 {
+                                return Err(Error::DivideByZero { span: *span });
+                            }
+                            l.checked_div(*r)
+                                .map(Value::Integer)
+                                .ok_or(Error::Overflow { span: *span })
+                        }
+                        Operator::Modulo => {
+                            if *r == 0 {
+                                return Err(Error::DivideByZero { span: *span });
+                            }
+                            l.checked_rem(*r)
+                                .map(Value::Integer)
+                                .ok_or(Error::Overflow { span: *span })
+                        }
+                        Operator::Equal => Ok(Value::Bool(l == r)),
+                        Operator::NotEqual => Ok(Value::Bool(l != r)),
+                        Operator::Less => Ok(Value::Bool(l < r)),
+                        Operator::Greater => Ok(Value::Bool(l > r)),
+                        Operator::LessEqual => Ok(Value::Bool(l <= r)),
+                        Operator::GreaterEqual => Ok(Value::Bool(l >= r)),
+                    };
                 }
 
                 // Promote to float if both weren't integers
@@ -141,50 +436,453 @@ impl Program {
                 };
 
                 if let (Value::Float(l), Value::Float(r)) = (left_val, right_val) {
-                    return match op {
+                    return Ok(match op {
                         Operator::Plus => Value::Float(l + r),
                         Operator::Minus => Value::Float(l - r),
                         Operator::Multiply => Value::Float(l * r),
                         Operator::Divide => Value::Float(l / r),
-                    };
+                        Operator::Modulo => Value::Float(l % r),
+                        Operator::Equal => Value::Bool(l == r),
+                        Operator::NotEqual => Value::Bool(l != r),
+                        Operator::Less => Value::Bool(l < r),
+                        Operator::Greater => Value::Bool(l > r),
+                        Operator::LessEqual => Value::Bool(l <= r),
+                        Operator::GreaterEqual => Value::Bool(l >= r),
+                    });
                 }
 
-                panic!("Unsupported operand types for binary operation");
+                Err(Error::TypeMismatch {
+                    expected: "matching numeric or string operands".to_owned(),
+                    actual: "incompatible operand types".to_owned(),
+                    span: *span,
+                })
             }
-            _ => panic!("Unsupported expression type"),
+            n => panic!("Unsupported expression type: {:#?}", n),
         }
     }
 
-    fn call_function(&self, name: &str, args: &[AstNode]) -> Option<Value> {
+    fn call_function(
+        &mut self,
+        name: &str,
+        args: &[AstNode],
+        span: Span,
+    ) -> Result<Option<Value>, Error> {
         let mut arg_values = args
             .iter()
             .map(|arg| self.compute_expression(arg))
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, _>>()?;
         if let Some(func) = self.builtins.get(name) {
-            func(&mut arg_values)
+            let func = *func;
+            func(&mut arg_values, span)
+        } else if let Some(fdef) = self.functions.get(name).cloned() {
+            if fdef.params.len() != arg_values.len() {
+                return Err(Error::WrongArgCount {
+                    expected: fdef.params.len(),
+                    actual: arg_values.len(),
+                    span,
+                });
+            }
+            let scope = fdef
+                .params
+                .iter()
+                .cloned()
+                .zip(arg_values)
+                .collect::<HashMap<_, _>>();
+            self.vars.push(scope);
+            let result = self.execute_function_body(&fdef.body);
+            self.vars.pop();
+            result
         } else {
-            panic!("Undefined function: {}", name);
+            Err(Error::UndefinedFunction {
+                name: name.to_owned(),
+                span,
+            })
         }
     }
 
-    pub fn execute(&mut self) {
-        let block = Rc::clone(&self.block);
+    /// Executes a block of statements, returning early with `Some(value)` if a
+    /// `return` statement is hit.
+    fn execute_block(&mut self, block: &Rc<Vec<AstNode>>) -> Result<Option<Value>, Error> {
         for node in block.iter() {
-            match node {
-                AstNode::Assign(var, expr) => {
-                    trace!("Assigning to variable: {}", var);
-                    let value = self.compute_expression(expr);
-                    self.vars.insert(var.clone(), value.clone());
+            if let Some(value) = self.execute_statement(node)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Executes a function body, returning the explicit `return` value if one
+    /// fires, or else the value of a trailing bare expression as an implicit
+    /// return, mirroring the "last expression is the result" convention from
+    /// languages like Rhai. A body that falls off the end without either
+    /// yields no value, same as a function with no `return`.
+    fn execute_function_body(&mut self, body: &Rc<Vec<AstNode>>) -> Result<Option<Value>, Error> {
+        self.execute_tail_block(body)
+    }
+
+    /// Like `execute_function_body`, but also usable for a nested block: runs
+    /// every statement but the last normally, then resolves the last one as a
+    /// tail position so a trailing `if`/`else` propagates its chosen branch's
+    /// own tail value instead of requiring an explicit `return`.
+    fn execute_tail_block(&mut self, block: &Rc<Vec<AstNode>>) -> Result<Option<Value>, Error> {
+        for (i, node) in block.iter().enumerate() {
+            if i == block.len() - 1 {
+                return self.execute_tail_statement(node);
+            }
+            if let Some(value) = self.execute_statement(node)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Resolves a statement in tail position: a bare expression yields its
+    /// value, a trailing `if`/`else` recurses into whichever branch ran so
+    /// its last expression propagates the same way, and anything else falls
+    /// back to ordinary statement execution (e.g. an explicit `return`).
+    fn execute_tail_statement(&mut self, node: &AstNode) -> Result<Option<Value>, Error> {
+        match node {
+            AstNode::ExprStatement(expr) => {
+                // A bare function call may legitimately return nothing, in
+                // which case the block has no implicit return value.
+                if let AstNode::FunctionCall(name, args, span) = expr.as_ref() {
+                    self.call_function(name, args, *span)
+                } else {
+                    Ok(Some(self.compute_expression(expr)?))
+                }
+            }
+            AstNode::If(cond, then_block, else_block, span) => {
+                let cond_val = self.compute_expression(cond)?;
+                let Value::Bool(cond) = cond_val else {
+                    return Err(Error::TypeMismatch {
+                        expected: "bool".to_owned(),
+                        actual: value_type_name(&cond_val).to_owned(),
+                        span: *span,
+                    });
+                };
+                if cond {
+                    self.execute_tail_block(then_block)
+                } else if let Some(else_block) = else_block {
+                    self.execute_tail_block(else_block)
+                } else {
+                    Ok(None)
+                }
+            }
+            _ => self.execute_statement(node),
+        }
+    }
+
+    /// Executes a single statement, returning `Some(value)` only when it was
+    /// a `return` that should unwind the enclosing block.
+    fn execute_statement(&mut self, node: &AstNode) -> Result<Option<Value>, Error> {
+        match node {
+            AstNode::Assign(var, expr) => {
+                trace!("Assigning to variable: {}", var);
+                let value = self.compute_expression(expr)?;
+                self.vars.last_mut().unwrap().insert(var.clone(), value);
+            }
+            AstNode::ExprStatement(expr) => {
+                // A bare function call may legitimately return nothing; any
+                // other expression used as a statement always yields a value,
+                // which is simply discarded here.
+                if let AstNode::FunctionCall(name, args, span) = expr.as_ref() {
+                    self.call_function(name, args, *span)?;
+                } else {
+                    self.compute_expression(expr)?;
+                }
+            }
+            AstNode::FunctionDef(name, params, body) => {
+                trace!("Defining function: {}", name);
+                self.functions.insert(
+                    name.clone(),
+                    Rc::new(FunctionDef {
+                        params: params.clone(),
+                        body: Rc::clone(body),
+                    }),
+                );
+            }
+            AstNode::Return(expr) => {
+                return Ok(Some(self.compute_expression(expr)?));
+            }
+            AstNode::IndexAssign(list_expr, index_expr, value_expr, span) => {
+                let Value::List(list) = self.compute_expression(list_expr)? else {
+                    return Err(Error::TypeMismatch {
+                        expected: "list".to_owned(),
+                        actual: "non-list".to_owned(),
+                        span: *span,
+                    });
+                };
+                let index_val = self.compute_expression(index_expr)?;
+                let Value::Integer(index) = index_val else {
+                    return Err(Error::TypeMismatch {
+                        expected: "integer".to_owned(),
+                        actual: value_type_name(&index_val).to_owned(),
+                        span: *span,
+                    });
+                };
+                let value = self.compute_expression(value_expr)?;
+                let mut list = list.borrow_mut();
+                let len = list.len();
+                let slot = list.get_mut(index as usize).ok_or_else(|| Error::TypeMismatch {
+                    expected: format!("index within 0..{}", len),
+                    actual: index.to_string(),
+                    span: *span,
+                })?;
+                *slot = value;
+            }
+            AstNode::ForIn(var, iterable, body, span) => {
+                let iterable_val = self.compute_expression(iterable)?;
+                let Value::List(list) = iterable_val else {
+                    return Err(Error::TypeMismatch {
+                        expected: "list".to_owned(),
+                        actual: value_type_name(&iterable_val).to_owned(),
+                        span: *span,
+                    });
+                };
+                let items = list.borrow().clone();
+                for item in items {
+                    self.vars.last_mut().unwrap().insert(var.clone(), item);
+                    if let Some(value) = self.execute_block(body)? {
+                        return Ok(Some(value));
+                    }
                 }
-                AstNode::FunctionCall(name, args) => {
-                    trace!("Calling function: {}", name);
-                    self.call_function(name, args);
+            }
+            AstNode::If(cond, then_block, else_block, span) => {
+                let cond_val = self.compute_expression(cond)?;
+                let Value::Bool(cond) = cond_val else {
+                    return Err(Error::TypeMismatch {
+                        expected: "bool".to_owned(),
+                        actual: value_type_name(&cond_val).to_owned(),
+                        span: *span,
+                    });
+                };
+                if cond {
+                    if let Some(value) = self.execute_block(then_block)? {
+                        return Ok(Some(value));
+                    }
+                } else if let Some(else_block) = else_block {
+                    if let Some(value) = self.execute_block(else_block)? {
+                        return Ok(Some(value));
+                    }
+                }
+            }
+            n => {
+                panic!("Unexpected AST node during execution: {:#?}", n);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn execute(&mut self) -> Result<(), Error> {
+        let block = Rc::clone(&self.block);
+        self.execute_block(&block)?;
+        Ok(())
+    }
+
+    /// Tokenizes and parses a single line against the retained environment,
+    /// executing it immediately. A bare expression's value is printed (using
+    /// the same formatting as the `print` builtin) rather than discarded.
+    pub fn eval_line(&mut self, src: &str) -> Result<(), Error> {
+        let tokens = tokenizer::tokenize(src)?;
+        let block = ast_parser::parse(&tokens)?;
+        for node in &block {
+            match &node {
+                AstNode::ExprStatement(expr) => {
+                    let value = if let AstNode::FunctionCall(name, args, span) = expr.as_ref() {
+                        self.call_function(name, args, *span)?
+                    } else {
+                        Some(self.compute_expression(expr)?)
+                    };
+                    if let Some(value) = value {
+                        builtin_print(&mut [value], Span::default())?;
+                    }
                 }
-                n => {
-                    self.compute_expression(n);
-                    panic!("Unexpected AST node during execution: {:#?}", node);
+                other => {
+                    self.execute_statement(other)?;
                 }
             }
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tokenizes, parses and executes a script against a fresh `Program`,
+    /// returning it so the test can inspect the resulting variables.
+    fn run(src: &str) -> Program {
+        let tokens = tokenizer::tokenize(src).unwrap();
+        let block = ast_parser::parse(&tokens).unwrap();
+        let mut program = Program::new(block);
+        program.execute().unwrap();
+        program
+    }
+
+    fn run_err(src: &str) -> Error {
+        let tokens = tokenizer::tokenize(src).unwrap();
+        let block = ast_parser::parse(&tokens).unwrap();
+        Program::new(block).execute().unwrap_err()
+    }
+
+    #[test]
+    fn recursive_function_with_explicit_return() {
+        let program = run("fn fact(n) {\n\
+             if n <= 1 {\n\
+             return 1\n\
+             }\n\
+             return n * fact(n - 1)\n\
+             }\n\
+             result = fact(5)");
+        assert_eq!(program.get_var("result"), Some(Value::Integer(120)));
+    }
+
+    #[test]
+    fn implicit_return_propagates_trailing_expression() {
+        let program = run("fn addone(n) {\n\
+             n + 1\n\
+             }\n\
+             result = addone(41)");
+        assert_eq!(program.get_var("result"), Some(Value::Integer(42)));
+    }
+
+    #[test]
+    fn if_else_selects_branch_and_propagates_tail_value() {
+        let program = run("fn sign(n) {\n\
+             if n < 0 {\n\
+             -1\n\
+             } else {\n\
+             1\n\
+             }\n\
+             }\n\
+             neg = sign(-5)\n\
+             pos = sign(5)");
+        assert_eq!(program.get_var("neg"), Some(Value::Integer(-1)));
+        assert_eq!(program.get_var("pos"), Some(Value::Integer(1)));
+    }
+
+    #[test]
+    fn for_in_range_accumulates_sum() {
+        let program = run("sum = 0\n\
+             for i in range(5) {\n\
+             sum = sum + i\n\
+             }");
+        assert_eq!(program.get_var("sum"), Some(Value::Integer(10)));
+    }
+
+    #[test]
+    fn list_index_read_and_nested_write() {
+        let program = run("xs = [[1, 2], [3, 4]]\n\
+             xs[0][1] = 9\n\
+             first = xs[0][1]\n\
+             second = xs[1][0]");
+        assert_eq!(program.get_var("first"), Some(Value::Integer(9)));
+        assert_eq!(program.get_var("second"), Some(Value::Integer(3)));
+    }
+
+    #[test]
+    fn unary_negation_and_not() {
+        let program = run("a = -5\n\
+             b = !true");
+        assert_eq!(program.get_var("a"), Some(Value::Integer(-5)));
+        assert_eq!(program.get_var("b"), Some(Value::Bool(false)));
+    }
+
+    #[test]
+    fn modulo_computes_remainder() {
+        let program = run("r = 7 % 3");
+        assert_eq!(program.get_var("r"), Some(Value::Integer(1)));
+    }
+
+    #[test]
+    fn divide_and_modulo_by_zero_error_cleanly() {
+        assert!(matches!(run_err("x = 1 / 0"), Error::DivideByZero { .. }));
+        assert!(matches!(run_err("x = 1 % 0"), Error::DivideByZero { .. }));
+    }
+
+    #[test]
+    fn integer_arithmetic_overflow_errors_cleanly() {
+        assert!(matches!(
+            run_err("x = 9223372036854775807\ny = x + 1"),
+            Error::Overflow { .. }
+        ));
+        assert!(matches!(
+            run_err("x = -9223372036854775807\ny = x - 2"),
+            Error::Overflow { .. }
+        ));
+        assert!(matches!(
+            run_err("x = -9223372036854775807 - 1\ny = x / -1"),
+            Error::Overflow { .. }
+        ));
+    }
+
+    #[test]
+    fn ord_and_chr_round_trip() {
+        let mut args = [Value::String("A".into())];
+        assert_eq!(
+            builtin_ord(&mut args, Span::default()).unwrap(),
+            Some(Value::Integer(65))
+        );
+
+        let mut args = [Value::Integer(65)];
+        assert_eq!(
+            builtin_chr(&mut args, Span::default()).unwrap(),
+            Some(Value::String("A".into()))
+        );
+    }
+
+    #[test]
+    fn join_concatenates_strings_with_separator() {
+        let mut args = [
+            Value::List(Rc::new(RefCell::new(vec![
+                Value::String("a".into()),
+                Value::String("b".into()),
+            ]))),
+            Value::String("-".into()),
+        ];
+        assert_eq!(
+            builtin_join(&mut args, Span::default()).unwrap(),
+            Some(Value::String("a-b".into()))
+        );
+    }
+
+    #[test]
+    fn ord_rejects_empty_string() {
+        let mut args = [Value::String("".into())];
+        assert!(matches!(
+            builtin_ord(&mut args, Span::default()),
+            Err(Error::Builtin { .. })
+        ));
+    }
+
+    #[test]
+    fn join_rejects_non_string_list_elements() {
+        let mut args = [
+            Value::List(Rc::new(RefCell::new(vec![
+                Value::Integer(1),
+                Value::Integer(2),
+            ]))),
+            Value::String(",".into()),
+        ];
+        assert!(matches!(
+            builtin_join(&mut args, Span::default()),
+            Err(Error::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn eval_line_assignment_persists_across_calls() {
+        let mut program = Program::new(Vec::new());
+        program.eval_line("x = 5").unwrap();
+        program.eval_line("x = x + 1").unwrap();
+        assert_eq!(program.get_var("x"), Some(Value::Integer(6)));
+    }
+
+    #[test]
+    fn eval_line_bare_expression_is_printed_not_stored() {
+        let mut program = Program::new(Vec::new());
+        assert!(program.eval_line("1 + 2").is_ok());
+        assert_eq!(program.get_var("1 + 2"), None);
     }
 }
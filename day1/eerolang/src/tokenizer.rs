@@ -2,8 +2,16 @@ use std::{cell::RefCell, rc::Rc};
 
 use log::trace;
 
+use crate::error::{Error, Span};
+
 #[derive(Debug, Clone, PartialEq)]
-pub enum Token {
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
     Assign,
     Operator(Operator),
     LParen,
@@ -17,6 +25,11 @@ pub enum Token {
     Ident(String),
     KeywordFor,
     KeywordIn,
+    KeywordFn,
+    KeywordReturn,
+    KeywordIf,
+    KeywordElse,
+    Bang,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -25,46 +38,121 @@ pub enum Operator {
     Minus,
     Multiply,
     Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
 }
 
 impl Operator {
     pub fn precedence(&self) -> u8 {
         match self {
-            Operator::Plus | Operator::Minus => 0,
-            Operator::Multiply | Operator::Divide => 1,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::Greater
+            | Operator::LessEqual
+            | Operator::GreaterEqual => 0,
+            Operator::Plus | Operator::Minus => 1,
+            Operator::Multiply | Operator::Divide | Operator::Modulo => 2,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Neg,
+    Not,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Integer(i64),
     Float(f64),
     String(Rc<str>),
     List(Rc<RefCell<Vec<Value>>>),
+    Bool(bool),
+}
+
+/// Finds the 1-indexed line/column of a byte offset into `source`.
+fn line_col(source: &str, byte_pos: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for ch in source[..byte_pos].chars() {
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
 }
 
-pub fn tokenize(source: &str) -> Vec<Token> {
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
     let mut tokens = Vec::new();
-    let mut iter = source.chars().peekable();
+    let mut iter = source.char_indices().peekable();
 
     let mut tbuf = String::new();
-    while let Some(ch) = iter.next() {
+    while let Some((start, ch)) = iter.next() {
+        let (line, column) = line_col(source, start);
+        let span = Span::new(line, column);
+        macro_rules! push {
+            ($kind:expr) => {
+                tokens.push(Token { kind: $kind, span })
+            };
+        }
+
         match ch {
-            '=' => tokens.push(Token::Assign),
-            '+' => tokens.push(Token::Operator(Operator::Plus)),
-            '-' => tokens.push(Token::Operator(Operator::Minus)),
-            '*' => tokens.push(Token::Operator(Operator::Multiply)),
-            '/' => tokens.push(Token::Operator(Operator::Divide)),
-            '(' => tokens.push(Token::LParen),
-            ')' => tokens.push(Token::RParen),
-            '[' => tokens.push(Token::LSquareParen),
-            ']' => tokens.push(Token::RSquareParen),
-            '{' => tokens.push(Token::LBrace),
-            '}' => tokens.push(Token::RBrace),
-            ',' => tokens.push(Token::Comma),
+            '=' => {
+                if iter.peek().map(|&(_, c)| c) == Some('=') {
+                    iter.next();
+                    push!(TokenKind::Operator(Operator::Equal));
+                } else {
+                    push!(TokenKind::Assign);
+                }
+            }
+            '!' => {
+                if iter.peek().map(|&(_, c)| c) == Some('=') {
+                    iter.next();
+                    push!(TokenKind::Operator(Operator::NotEqual));
+                } else {
+                    push!(TokenKind::Bang);
+                }
+            }
+            '<' => {
+                if iter.peek().map(|&(_, c)| c) == Some('=') {
+                    iter.next();
+                    push!(TokenKind::Operator(Operator::LessEqual));
+                } else {
+                    push!(TokenKind::Operator(Operator::Less));
+                }
+            }
+            '>' => {
+                if iter.peek().map(|&(_, c)| c) == Some('=') {
+                    iter.next();
+                    push!(TokenKind::Operator(Operator::GreaterEqual));
+                } else {
+                    push!(TokenKind::Operator(Operator::Greater));
+                }
+            }
+            '+' => push!(TokenKind::Operator(Operator::Plus)),
+            '-' => push!(TokenKind::Operator(Operator::Minus)),
+            '*' => push!(TokenKind::Operator(Operator::Multiply)),
+            '/' => push!(TokenKind::Operator(Operator::Divide)),
+            '%' => push!(TokenKind::Operator(Operator::Modulo)),
+            '(' => push!(TokenKind::LParen),
+            ')' => push!(TokenKind::RParen),
+            '[' => push!(TokenKind::LSquareParen),
+            ']' => push!(TokenKind::RSquareParen),
+            '{' => push!(TokenKind::LBrace),
+            '}' => push!(TokenKind::RBrace),
+            ',' => push!(TokenKind::Comma),
             '#' => {
-                while let Some(&next_ch) = iter.peek() {
+                while let Some(&(_, next_ch)) = iter.peek() {
                     if next_ch == '\n' {
                         break;
                     }
@@ -74,7 +162,7 @@ pub fn tokenize(source: &str) -> Vec<Token> {
             '"' => {
                 tbuf.clear();
                 let mut escape = false;
-                for next_ch in iter.by_ref() {
+                for (_, next_ch) in iter.by_ref() {
                     if next_ch == '\\' && !escape {
                         escape = true;
                         continue;
@@ -96,12 +184,12 @@ pub fn tokenize(source: &str) -> Vec<Token> {
                     }
                     escape = false;
                 }
-                tokens.push(Token::Literal(Value::String(tbuf.clone().into())));
+                push!(TokenKind::Literal(Value::String(tbuf.clone().into())));
                 tbuf.clear();
             }
             ch if ch.is_alphabetic() => {
                 tbuf.push(ch);
-                while let Some(&next_ch) = iter.peek() {
+                while let Some(&(_, next_ch)) = iter.peek() {
                     if next_ch.is_alphanumeric() {
                         tbuf.push(next_ch);
                         iter.next();
@@ -110,16 +198,22 @@ pub fn tokenize(source: &str) -> Vec<Token> {
                     }
                 }
                 match tbuf.as_str() {
-                    "for" => tokens.push(Token::KeywordFor),
-                    "in" => tokens.push(Token::KeywordIn),
-                    _ => tokens.push(Token::Ident(tbuf.clone())),
+                    "for" => push!(TokenKind::KeywordFor),
+                    "in" => push!(TokenKind::KeywordIn),
+                    "fn" => push!(TokenKind::KeywordFn),
+                    "return" => push!(TokenKind::KeywordReturn),
+                    "if" => push!(TokenKind::KeywordIf),
+                    "else" => push!(TokenKind::KeywordElse),
+                    "true" => push!(TokenKind::Literal(Value::Bool(true))),
+                    "false" => push!(TokenKind::Literal(Value::Bool(false))),
+                    _ => push!(TokenKind::Ident(tbuf.clone())),
                 }
                 tbuf.clear();
             }
             ch if ch.is_ascii_digit() => {
                 tbuf.push(ch);
                 let mut is_float = false;
-                while let Some(&next_ch) = iter.peek() {
+                while let Some(&(_, next_ch)) = iter.peek() {
                     if next_ch.is_ascii_digit() {
                         tbuf.push(next_ch);
                         iter.next();
@@ -132,26 +226,58 @@ pub fn tokenize(source: &str) -> Vec<Token> {
                     }
                 }
                 if is_float {
-                    if let Ok(float_val) = tbuf.parse::<f64>() {
-                        tokens.push(Token::Literal(Value::Float(float_val)));
-                    } else {
-                        panic!("Invalid float literal: {}", tbuf);
-                    }
-                } else if let Ok(int_val) = tbuf.parse::<i64>() {
-                    tokens.push(Token::Literal(Value::Integer(int_val)));
+                    let Ok(float_val) = tbuf.parse::<f64>() else {
+                        return Err(Error::UnexpectedToken {
+                            found: format!("invalid float literal: {}", tbuf),
+                            span,
+                        });
+                    };
+                    push!(TokenKind::Literal(Value::Float(float_val)));
                 } else {
-                    panic!("Invalid integer literal: {}", tbuf);
+                    let Ok(int_val) = tbuf.parse::<i64>() else {
+                        return Err(Error::UnexpectedToken {
+                            found: format!("invalid integer literal: {}", tbuf),
+                            span,
+                        });
+                    };
+                    push!(TokenKind::Literal(Value::Integer(int_val)));
                 }
                 tbuf.clear();
             }
             ch if ch.is_whitespace() => {}
             _ => {
-                panic!("Unexpected character: {}", ch);
+                return Err(Error::UnexpectedChar { ch, span });
             }
         }
     }
 
     trace!("Tokenized source:\n{:#?}", tokens);
 
-    tokens
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn true_and_false_tokenize_as_bool_literals() {
+        let tokens = tokenize("true false").unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Literal(Value::Bool(true)));
+        assert_eq!(tokens[1].kind, TokenKind::Literal(Value::Bool(false)));
+    }
+
+    #[test]
+    fn tracks_line_and_column_across_newlines() {
+        let tokens = tokenize("x = 1\ny = 2").unwrap();
+        let spans: Vec<Span> = tokens.iter().map(|t| t.span).collect();
+        assert_eq!(spans[0], Span::new(1, 1)); // x
+        assert_eq!(spans[3], Span::new(2, 1)); // y
+    }
+
+    #[test]
+    fn unexpected_char_reports_its_span() {
+        let err = tokenize("x = 1\n@").unwrap_err();
+        assert_eq!(err.span(), Span::new(2, 1));
+    }
 }